@@ -1,14 +1,13 @@
 //! Defines the Light data structure
 
-use crate::{AiString, Color3D, Vector3D};
+use crate::{AiString, Color3D, Vector2D, Vector3D};
 use libc::c_float;
 
 /// Enumerates all supported types of light sources.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-
+#[repr(i32)]
 pub enum LightType {
     /// An undefined light, not a valid value
-    // TODO handle this in a rust way?
     Undefined = 0x0,
 
     /// A directional light source has a well-defined direction.
@@ -31,6 +30,18 @@ pub enum LightType {
     /// A good example for a spot light is a light spot in
     /// sport arenas.
     Spot = 0x3,
+
+    /// An ambient light that contributes a constant amount of
+    /// light to the scene regardless of position or direction.
+    Ambient = 0x4,
+
+    /// An area light is a rectangular light source.
+    ///
+    /// It has a position and direction like a spot or directional
+    /// light, plus a `size` giving its extent. Some renderers
+    /// support this light type, e.g. the glTF and FBX formats
+    /// can export it.
+    Area = 0x5,
 }
 
 /// Helper structure to describe a light source.
@@ -46,7 +57,7 @@ pub enum LightType {
 /// then, the transformation tracks of the main node make the
 /// spot light already point in the right direction.
 #[derive(Copy, Clone, PartialEq, Debug)]
-
+#[repr(C)]
 pub struct Light {
     /// The name of the light source.
     ///
@@ -73,6 +84,12 @@ pub struct Light {
     /// may be normalized, but it needn't.
     pub direction: Vector3D,
 
+    /// Up vector of the light source in space. Relative to the
+    /// transformation of the node corresponding to the light.
+    ///
+    /// Undefined for point lights.
+    pub up: Vector3D,
+
     /// Constant light attenuation factor.
     ///
     /// The intensity of the light source at a given distance 'd' from
@@ -145,6 +162,443 @@ pub struct Light {
     /// It is assumed that the application uses a smooth
     /// interpolation between the inner and the outer cone of the spot light.
     pub angle_outer_cone: c_float,
+
+    /// Size of the area light source.
+    ///
+    /// Only meaningful for `LightType::Area`, where it gives the
+    /// width and height of the rectangular emitter in local space.
+    /// It is `(0, 0)` for every other light type.
+    pub size: Vector2D,
+}
+
+impl Light {
+    /// Classifies this light and returns a type-safe view over the
+    /// fields that are actually meaningful for its `light_type`.
+    ///
+    /// `Light` is a flat FFI-mirroring struct where, depending on
+    /// `light_type`, some fields are simply undefined (e.g. `direction`
+    /// for a point light). `classify` reads those only where they are
+    /// valid and returns `None` for `LightType::Undefined`, which is
+    /// not a valid value to begin with.
+    pub fn classify(&self) -> Option<SceneLight> {
+        let colors = LightColors {
+            diffuse: self.color_diffuse,
+            specular: self.color_specular,
+            ambient: self.color_ambient,
+        };
+
+        match self.light_type {
+            LightType::Undefined => None,
+            LightType::Directional => Some(SceneLight::Directional {
+                direction: self.direction,
+                colors,
+            }),
+            LightType::Point => Some(SceneLight::Point {
+                position: self.position,
+                attenuation: Attenuation {
+                    constant: self.attenuation_constant,
+                    linear: self.attenuation_linear,
+                    quadratic: self.attenuation_quadratic,
+                },
+                colors,
+            }),
+            LightType::Spot => Some(SceneLight::Spot {
+                position: self.position,
+                direction: self.direction,
+                attenuation: Attenuation {
+                    constant: self.attenuation_constant,
+                    linear: self.attenuation_linear,
+                    quadratic: self.attenuation_quadratic,
+                },
+                inner_cone: self.angle_inner_cone,
+                outer_cone: self.angle_outer_cone,
+                colors,
+            }),
+            LightType::Ambient => Some(SceneLight::Ambient { colors }),
+            LightType::Area => Some(SceneLight::Area {
+                position: self.position,
+                direction: self.direction,
+                size: self.size,
+                colors,
+            }),
+        }
+    }
+
+    /// Evaluates the light's attenuation at a given `distance` from its
+    /// position.
+    ///
+    /// ```math
+    /// Atten = 1/( att0 + att1 * d + att2 * d*d)
+    /// ```
+    ///
+    /// Attenuation is undefined for `Directional` and `Ambient` lights,
+    /// which are not attenuated over distance; `1.0` is returned for
+    /// those instead.
+    pub fn attenuation_at(&self, distance: c_float) -> c_float {
+        match self.light_type {
+            LightType::Directional | LightType::Ambient => 1.0,
+            _ => {
+                1.0 / (self.attenuation_constant
+                    + self.attenuation_linear * distance
+                    + self.attenuation_quadratic * distance * distance)
+            }
+        }
+    }
+
+    /// Computes the distance at which this light's attenuation drops
+    /// below `threshold`, for use in light culling.
+    ///
+    /// Returns `None` if the light has infinite range. This is always
+    /// the case for `Directional` and `Ambient` lights, whose
+    /// attenuation factors are undefined (consistent with
+    /// `attenuation_at` treating them as unattenuated), and also holds
+    /// for any other light whose linear and quadratic attenuation
+    /// factors are both zero, or whose `threshold` is zero.
+    ///
+    /// `None` is also returned when the quadratic has no real root
+    /// (negative discriminant): the attenuation then stays below
+    /// `threshold` at every distance, so the range is not infinite.
+    pub fn effective_range(&self, threshold: c_float) -> Option<c_float> {
+        if let LightType::Directional | LightType::Ambient = self.light_type {
+            return None;
+        }
+
+        if threshold == 0.0 {
+            return None;
+        }
+
+        let a = self.attenuation_quadratic;
+        let b = self.attenuation_linear;
+        let c = self.attenuation_constant - 1.0 / threshold;
+
+        if a == 0.0 && b == 0.0 {
+            return None;
+        }
+
+        if a == 0.0 {
+            return Some(-c / b);
+        }
+
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        Some((-b + discriminant.sqrt()) / (2.0 * a))
+    }
+
+    /// Evaluates the cone attenuation factor of a `Spot` light towards
+    /// `to_point`, a direction from the light to the shaded point.
+    ///
+    /// The cone angles are full opening angles, so the angle between
+    /// `self.direction` and `to_point` is compared against half of each.
+    /// Returns `1.0` when that angle is at most `angle_inner_cone / 2`,
+    /// `0.0` when it is at least `angle_outer_cone / 2`, and a smooth
+    /// (`smoothstep`) interpolation
+    /// between those two thresholds otherwise. Returns `1.0` for
+    /// non-spot lights so this composes cleanly with `attenuation_at`.
+    pub fn spot_falloff(&self, to_point: Vector3D) -> c_float {
+        if self.light_type != LightType::Spot {
+            return 1.0;
+        }
+
+        let dir = normalize(self.direction);
+        let to_point = normalize(to_point);
+        let cos_angle = dir.x * to_point.x + dir.y * to_point.y + dir.z * to_point.z;
+        let cos_inner = (self.angle_inner_cone / 2.0).cos();
+        let cos_outer = (self.angle_outer_cone / 2.0).cos();
+
+        if cos_angle >= cos_inner {
+            1.0
+        } else if cos_angle <= cos_outer {
+            0.0
+        } else {
+            let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+/// Normalizes a vector to unit length.
+///
+/// Returns the zero vector for a zero-length input instead of dividing
+/// by zero, so degenerate `direction`/`to_point` inputs to `spot_falloff`
+/// cannot propagate `NaN` into its result.
+fn normalize(v: Vector3D) -> Vector3D {
+    let len = (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
+    if len == 0.0 {
+        return Vector3D {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+    }
+
+    Vector3D {
+        x: v.x / len,
+        y: v.y / len,
+        z: v.z / len,
+    }
+}
+
+/// The diffuse, specular and ambient color contributions of a light.
+///
+/// Bundled together since every `SceneLight` variant that emits light
+/// carries all three.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LightColors {
+    /// Diffuse color of the light source. See `Light::color_diffuse`.
+    pub diffuse: Color3D,
+
+    /// Specular color of the light source. See `Light::color_specular`.
+    pub specular: Color3D,
+
+    /// Ambient color of the light source. See `Light::color_ambient`.
+    pub ambient: Color3D,
+}
+
+/// The attenuation factors of a light that has a position in space.
+///
+/// See `Light::attenuation_constant` for the attenuation equation these
+/// factors plug into.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Attenuation {
+    /// Constant attenuation factor (att0).
+    pub constant: c_float,
+
+    /// Linear attenuation factor (att1).
+    pub linear: c_float,
+
+    /// Quadratic attenuation factor (att2).
+    pub quadratic: c_float,
+}
+
+/// A type-safe view of a light source, exposing only the parameters
+/// that are meaningful for its kind.
+///
+/// Obtained via `Light::classify`. Unlike `Light`, which mirrors the
+/// flat `aiLight` FFI layout and carries every field regardless of
+/// `light_type`, each `SceneLight` variant only carries the fields that
+/// are actually defined for that kind of light.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SceneLight {
+    /// A directional light, e.g. sunlight.
+    Directional {
+        /// Direction the light is shining in.
+        direction: Vector3D,
+        /// Diffuse, specular and ambient color contributions.
+        colors: LightColors,
+    },
+
+    /// A point light that emits light in all directions.
+    Point {
+        /// Position of the light source in space.
+        position: Vector3D,
+        /// Attenuation of the light intensity over distance.
+        attenuation: Attenuation,
+        /// Diffuse, specular and ambient color contributions.
+        colors: LightColors,
+    },
+
+    /// A spot light that emits light in a cone.
+    Spot {
+        /// Position of the light source in space.
+        position: Vector3D,
+        /// Direction the light is pointing in.
+        direction: Vector3D,
+        /// Attenuation of the light intensity over distance.
+        attenuation: Attenuation,
+        /// Inner cone angle, in radians, within which the light has
+        /// maximum influence.
+        inner_cone: c_float,
+        /// Outer cone angle, in radians, outside of which the light
+        /// has no influence.
+        outer_cone: c_float,
+        /// Diffuse, specular and ambient color contributions.
+        colors: LightColors,
+    },
+
+    /// An ambient light contributing a constant term regardless of
+    /// geometry.
+    Ambient {
+        /// Diffuse, specular and ambient color contributions.
+        colors: LightColors,
+    },
+
+    /// A finite rectangular area light.
+    Area {
+        /// Position of the light source in space.
+        position: Vector3D,
+        /// Direction the light is pointing in.
+        direction: Vector3D,
+        /// Width and height of the rectangular emitter in local space.
+        size: Vector2D,
+        /// Diffuse, specular and ambient color contributions.
+        colors: LightColors,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a zeroed `Light` of the given type, with every other field
+    /// at its bit-pattern zero value. Good enough for exercising the
+    /// pure helper methods below, which only ever read a handful of
+    /// fields at a time.
+    fn blank_light(light_type: LightType) -> Light {
+        let mut light: Light = unsafe { std::mem::zeroed() };
+        light.light_type = light_type;
+        light
+    }
+
+    #[test]
+    fn classify_returns_none_for_undefined() {
+        let light = blank_light(LightType::Undefined);
+        assert!(light.classify().is_none());
+    }
+
+    #[test]
+    fn classify_directional_carries_direction() {
+        let mut light = blank_light(LightType::Directional);
+        light.direction = Vector3D {
+            x: 0.0,
+            y: -1.0,
+            z: 0.0,
+        };
+
+        match light.classify() {
+            Some(SceneLight::Directional { direction, .. }) => {
+                assert_eq!(direction, light.direction);
+            }
+            other => panic!("expected Directional, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn attenuation_at_is_unattenuated_for_directional_and_ambient() {
+        let directional = blank_light(LightType::Directional);
+        let ambient = blank_light(LightType::Ambient);
+
+        assert_eq!(directional.attenuation_at(1000.0), 1.0);
+        assert_eq!(ambient.attenuation_at(1000.0), 1.0);
+    }
+
+    #[test]
+    fn attenuation_at_evaluates_the_quadratic_model() {
+        let mut light = blank_light(LightType::Point);
+        light.attenuation_constant = 1.0;
+        light.attenuation_quadratic = 1.0;
+
+        assert_eq!(light.attenuation_at(3.0), 0.1);
+    }
+
+    #[test]
+    fn effective_range_is_none_for_directional_and_ambient() {
+        let directional = blank_light(LightType::Directional);
+        let ambient = blank_light(LightType::Ambient);
+
+        assert_eq!(directional.effective_range(0.01), None);
+        assert_eq!(ambient.effective_range(0.01), None);
+    }
+
+    #[test]
+    fn effective_range_is_none_for_zero_threshold() {
+        let mut light = blank_light(LightType::Point);
+        light.attenuation_constant = 1.0;
+
+        assert_eq!(light.effective_range(0.0), None);
+    }
+
+    #[test]
+    fn effective_range_is_none_for_infinite_range_light() {
+        let light = blank_light(LightType::Point);
+        assert_eq!(light.effective_range(0.01), None);
+    }
+
+    #[test]
+    fn effective_range_solves_the_linear_only_case() {
+        let mut light = blank_light(LightType::Point);
+        light.attenuation_constant = 1.0;
+        light.attenuation_linear = 2.0;
+
+        // threshold = 1/3 => c = att0 - 3 = -2 => root = -c / att1 = 1.0
+        assert_eq!(light.effective_range(1.0 / 3.0), Some(1.0));
+    }
+
+    #[test]
+    fn effective_range_is_none_for_negative_discriminant() {
+        let mut light = blank_light(LightType::Point);
+        light.attenuation_constant = 10.0;
+        light.attenuation_linear = 1.0;
+        light.attenuation_quadratic = 1.0;
+
+        assert_eq!(light.effective_range(1.0), None);
+    }
+
+    #[test]
+    fn spot_falloff_is_full_for_non_spot_lights() {
+        let light = blank_light(LightType::Point);
+        let to_point = Vector3D {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        };
+
+        assert_eq!(light.spot_falloff(to_point), 1.0);
+    }
+
+    #[test]
+    fn spot_falloff_is_full_inside_the_inner_cone() {
+        let mut light = blank_light(LightType::Spot);
+        light.direction = Vector3D {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        };
+        light.angle_inner_cone = 0.2;
+        light.angle_outer_cone = 0.6;
+
+        assert_eq!(light.spot_falloff(light.direction), 1.0);
+    }
+
+    #[test]
+    fn spot_falloff_is_zero_outside_the_outer_cone() {
+        let mut light = blank_light(LightType::Spot);
+        light.direction = Vector3D {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        };
+        light.angle_inner_cone = 0.2;
+        light.angle_outer_cone = 0.6;
+
+        let to_point = Vector3D {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        assert_eq!(light.spot_falloff(to_point), 0.0);
+    }
+
+    #[test]
+    fn spot_falloff_does_not_propagate_nan_for_zero_length_direction() {
+        let mut light = blank_light(LightType::Spot);
+        light.direction = Vector3D {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        light.angle_inner_cone = 0.2;
+        light.angle_outer_cone = 0.6;
+
+        let to_point = Vector3D {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        };
+        assert!(light.spot_falloff(to_point).is_finite());
+    }
 }
 
 // vim: et tw=78 sw=4: